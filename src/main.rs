@@ -1,18 +1,75 @@
+use std::collections::HashMap;
 use std::{env, vec};
 
+// A line/column location in a source string, computed from an absolute byte
+// offset so it stays meaningful regardless of how deeply that offset was
+// nested inside parentheses when the error was raised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    line: usize,
+    col: usize,
+}
+
+impl Position {
+    fn from_offset(source: &str, offset: usize) -> Self {
+        let mut line = 0;
+        let mut col = 0;
+        for (i, c) in source.char_indices() {
+            if i >= offset {
+                break;
+            }
+            if c == '\n' {
+                line += 1;
+                col = 0;
+            } else {
+                col += 1;
+            }
+        }
+        Self { line, col }
+    }
+}
+
 struct ParsingError {
     error: String,
-    base: Option<String>,
-    index: Option<usize>,
+    source: Option<String>,
+    // Where the error itself was detected.
+    at: Option<Position>,
+    // An earlier location the error relates to, e.g. the opening parenthesis
+    // a "not closed" error is complaining about.
+    related: Option<Position>,
 }
 
 impl ParsingError {
-    fn indexed(error: String, base: String, index: usize) -> Self {
-        Self { error, base: Some(base), index: Some(index) }
+    fn indexed(error: String, source: String, index: usize) -> Self {
+        let at = Position::from_offset(&source, index);
+        Self { error, source: Some(source), at: Some(at), related: None }
+    }
+
+    // Like `indexed`, but also points at `related_index`, e.g. the opening
+    // parenthesis that a "not closed" error was raised for.
+    fn spanned(error: String, source: String, index: usize, related_index: usize) -> Self {
+        let at = Position::from_offset(&source, index);
+        let related = Position::from_offset(&source, related_index);
+        Self { error, source: Some(source), at: Some(at), related: Some(related) }
     }
 
     fn not_indexed(error: String) -> Self {
-        Self { error, base: None, index: None }
+        Self { error, source: None, at: None, related: None }
+    }
+
+    // A caret line underlining every column in `cols` on the same source line.
+    fn caret_line(cols: &[usize]) -> String {
+        let width = cols.iter().max().copied().unwrap_or(0) + 1;
+        let mut line = vec![' '; width];
+        for &col in cols {
+            line[col] = '^';
+        }
+        line.into_iter().collect()
+    }
+
+    fn write_position(f: &mut std::fmt::Formatter<'_>, source: &str, pos: Position) -> std::fmt::Result {
+        writeln!(f, "{}", source.lines().nth(pos.line).unwrap_or(""))?;
+        writeln!(f, "{}", Self::caret_line(&[pos.col]))
     }
 }
 
@@ -20,8 +77,9 @@ impl std::fmt::Debug for ParsingError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ParsingError")
             .field("error", &self.error)
-            .field("base", &self.base)
-            .field("index", &self.index)
+            .field("source", &self.source)
+            .field("at", &self.at)
+            .field("related", &self.related)
             .finish()
     }
 }
@@ -29,19 +87,39 @@ impl std::fmt::Debug for ParsingError {
 impl std::fmt::Display for ParsingError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Error: {}", self.error)?;
-        match self.index {
-            Some(i) => {
-                writeln!(f, "{}", self.base.as_ref().unwrap())?;
-                writeln!(f, "{}^", " ".repeat(i))?;
+        let (Some(source), Some(at)) = (&self.source, self.at) else {
+            return Ok(());
+        };
+        match self.related {
+            // Both locations are on the same line: a single line with two
+            // carets reads clearer than two near-identical line dumps.
+            Some(related) if related.line == at.line => {
+                writeln!(f, "{}", source.lines().nth(at.line).unwrap_or(""))?;
+                writeln!(f, "{}", Self::caret_line(&[related.col, at.col]))?;
             }
-            None => (),
+            Some(related) => {
+                Self::write_position(f, source, related)?;
+                Self::write_position(f, source, at)?;
+            }
+            None => Self::write_position(f, source, at)?,
         }
         Ok(())
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Operator {
+    Assign,
+    BitOr,
+    BitAnd,
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Shl,
+    Shr,
     Add,
     Subtract,
     Multiply,
@@ -53,6 +131,17 @@ enum Operator {
 impl std::fmt::Display for Operator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::Assign => write!(f, "="),
+            Self::BitOr => write!(f, "|"),
+            Self::BitAnd => write!(f, "&"),
+            Self::Eq => write!(f, "=="),
+            Self::Neq => write!(f, "!="),
+            Self::Lt => write!(f, "<"),
+            Self::Lte => write!(f, "<="),
+            Self::Gt => write!(f, ">"),
+            Self::Gte => write!(f, ">="),
+            Self::Shl => write!(f, "<<"),
+            Self::Shr => write!(f, ">>"),
             Self::Add => write!(f, "+"),
             Self::Subtract => write!(f, "-"),
             Self::Multiply => write!(f, "*"),
@@ -68,6 +157,17 @@ impl std::str::FromStr for Operator {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
+            "=" => Ok(Self::Assign),
+            "|" => Ok(Self::BitOr),
+            "&" => Ok(Self::BitAnd),
+            "==" => Ok(Self::Eq),
+            "!=" => Ok(Self::Neq),
+            "<" => Ok(Self::Lt),
+            "<=" => Ok(Self::Lte),
+            ">" => Ok(Self::Gt),
+            ">=" => Ok(Self::Gte),
+            "<<" => Ok(Self::Shl),
+            ">>" => Ok(Self::Shr),
             "+" => Ok(Self::Add),
             "-" => Ok(Self::Subtract),
             "*" => Ok(Self::Multiply),
@@ -78,316 +178,412 @@ impl std::str::FromStr for Operator {
     }
 }
 
-impl PartialOrd for Operator {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.priority().cmp(&other.priority()))
-    }
-}
-
-impl Ord for Operator {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.priority().cmp(&other.priority())
-    }
-}
+// Binding power of a unary prefix operator (`-`, `+`), used by `parse_prefix`.
+// Higher than every infix operator's right binding power so e.g. `-2^2`
+// parses as `(-2)^2` rather than `-(2^2)`.
+const UNARY_BINDING_POWER: u8 = 100;
 
 impl Operator {
-    fn count(&self) -> i32 {
-        // Return the number of arguments this operator takes
+    // (left, right) binding power for this operator used as an infix in the
+    // Pratt parser. Left-associative operators bind their right-hand side
+    // one tighter than themselves (`lbp + 1`); `Pow`, being right-associative,
+    // binds its right-hand side at the same power as itself. `Assign` is also
+    // right-associative, so `a = b = 3` assigns to both.
+    fn binding_power(&self) -> (u8, u8) {
         match self {
-            Self::Add | Self::Subtract | Self::Multiply | Self::Divide | Self::Pow => 2,
-            Self::Inverse => 1,
+            Self::Assign => (10, 10),
+            Self::BitOr => (20, 21),
+            Self::BitAnd => (30, 31),
+            Self::Eq | Self::Neq => (40, 41),
+            Self::Lt | Self::Lte | Self::Gt | Self::Gte => (50, 51),
+            Self::Shl | Self::Shr => (60, 61),
+            Self::Add | Self::Subtract => (70, 71),
+            Self::Multiply | Self::Divide => (80, 81),
+            Self::Pow => (90, 90),
+            Self::Inverse => unreachable!("Inverse is a prefix-only operator, never infix"),
         }
     }
+}
+
+// A single lexical token plus the absolute byte offset in the source line
+// where it starts, so parse errors can point at the right column even
+// across nested parentheses.
+enum Lexeme {
+    Number(f64),
+    Operator(Operator),
+    LParen,
+    RParen,
+    Comma,
+    // A bare name: resolved as a variable, or as a function call if
+    // immediately followed by `LParen`.
+    Identifier(String),
+}
 
-    fn priority(&self) -> i32 {
-        // Return the priority of this operator
+impl std::fmt::Display for Lexeme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Self::Add | Self::Subtract => 1,
-            Self::Multiply | Self::Divide => 2,
-            Self::Pow => 3,
-            Self::Inverse => 4,
+            Self::Number(n) => write!(f, "{}", n),
+            Self::Operator(o) => write!(f, " {} ", o),
+            Self::LParen => write!(f, "("),
+            Self::RParen => write!(f, ")"),
+            Self::Comma => write!(f, ", "),
+            Self::Identifier(name) => write!(f, "{}", name),
         }
     }
-
 }
 
-enum ParsingToken {
-    Number(f64),
-    Operator(Operator),
-    Parenthesis(Vec<ParsingToken>),
-}
+// Single-pass lexer: walks the input once, left to right, producing a flat
+// stream of `(Lexeme, start_offset)` pairs. Parentheses are kept as explicit
+// `LParen`/`RParen` tokens instead of being sliced out and re-lexed, so
+// nesting is linear-time and every offset stays absolute to `input`.
+fn lex(input: &str) -> Result<Vec<(Lexeme, usize)>, ParsingError> {
+    let mut tokens: Vec<(Lexeme, usize)> = Vec::new();
+
+    let mut is_float = false;
+    let mut current_number = String::new();
+    let mut current_float = String::new();
+    let mut current_ident = String::new();
+    let mut ident_start = 0;
+
+    // Non-decimal integer literal currently being accumulated (base, digits, start offset)
+    let mut radix: Option<(u32, String, usize)> = None;
+    // Set by a two-character token (`==`, `!=`, `<=`, `>=`, `<<`, `>>`) to
+    // swallow its second character.
+    let mut skip_next = false;
+
+    let chars: Vec<char> = input.chars().collect();
+
+    let flush_number = |int_part: &mut String,
+                        float_part: &mut String,
+                        tokens: &mut Vec<(Lexeme, usize)>,
+                        is_float: &mut bool,
+                        start: usize,
+                        index: usize|
+     -> Result<(), ParsingError> {
+        if int_part.is_empty() && float_part.is_empty() {
+            return Ok(());
+        }
 
-impl ParsingToken {
-    fn build_number(int_part: &str, float_part: &str) -> Result<f64, String> {
         let mut number = String::new();
         number.push_str(int_part);
         number.push('.');
         number.push_str(float_part);
-        match number.parse() {
-            Ok(n) => Ok(n),
-            Err(_) => Err("Invalid number".to_string()),
-        }
-    }
-
-    fn tokenize(input: &str) -> Result<Vec<Self>, ParsingError> {
-        let mut tokens = Vec::new();
+        let number: f64 = number
+            .parse()
+            .map_err(|_| ParsingError::indexed("Invalid number".to_string(), input.to_string(), index))?;
 
-        let mut is_float = false;
-        let mut is_parsing_parentesis = false;
+        tokens.push((Lexeme::Number(number), start));
+        *int_part = String::new();
+        *float_part = String::new();
+        *is_float = false;
+        Ok(())
+    };
 
-        let mut current_number = String::new();
-        let mut current_float = String::new();
+    // Implicit multiplication: `2(3)` and `(2)3` are `2 * (3)` and `(2) * 3`,
+    // but a name directly before `(` is a function call, not a factor.
+    let needs_implicit_multiply = |tokens: &Vec<(Lexeme, usize)>| {
+        matches!(
+            tokens.last(),
+            Some((Lexeme::Number(_), _)) | Some((Lexeme::RParen, _))
+        )
+    };
 
-        let mut parenthesis_depth = 0;
-        let mut parenthesis_start = 0;
+    let mut number_start = 0;
+    for (i, &c) in chars.iter().enumerate() {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
 
-        let compute_number = |int_part: &mut String,
-                              float_part: &mut String,
-                              tokens: &mut Vec<ParsingToken>,
-                              is_float: &mut bool,
-                              index: usize|
-         -> Result<(), ParsingError> {
-            // if previous token is a parenthesis,
-            // we do not want to compute the number
-            // return Ok(()) if is_parsing_parentesis;
-            if tokens
-                .last()
-                .map_or(false, |t| matches!(t, Self::Parenthesis(_)))
-            {
-                return Ok(());
+        if let Some((base, ref mut digits, start)) = radix {
+            if c.is_digit(base) {
+                digits.push(c);
+                continue;
+            }
+            if digits.is_empty() {
+                return Err(ParsingError::indexed(
+                    "Invalid integer literal".to_string(),
+                    input.to_string(),
+                    i,
+                ));
             }
+            let value = i64::from_str_radix(digits, base).map_err(|_| {
+                ParsingError::indexed("Invalid integer literal".to_string(), input.to_string(), i)
+            })?;
+            tokens.push((Lexeme::Number(value as f64), start));
+            radix = None;
+            // Fall through: `c` still needs to be processed normally below.
+        }
 
-            let number = Self::build_number(int_part, float_part)
-                .map_err(|e| ParsingError::indexed(e, input.to_string(), index))?;
+        // An identifier is done growing once we hit '(' (a function call
+        // follows) or any character that can't be part of a name; in the
+        // latter case it stands alone as a variable reference.
+        if !current_ident.is_empty() && c != '(' && !(c.is_ascii_alphanumeric() || c == '_') {
+            tokens.push((Lexeme::Identifier(std::mem::take(&mut current_ident)), ident_start));
+        }
 
-            tokens.push(Self::Number(number));
-            *int_part = String::new();
-            *float_part = String::new();
-            *is_float = false;
-            Ok(())
-        };
+        if current_number.is_empty() && current_float.is_empty() && !is_float {
+            number_start = i;
+        }
 
-        for (i, c) in input.chars().enumerate() {
-            match c {
-                '0'..='9' => {
-                    if is_parsing_parentesis {
-                        continue;
-                    }
-                    // if last token is a parenthesis,
-                    // we add a multiplication operator
-                    if tokens
-                        .last()
-                        .map_or(false, |t| matches!(t, Self::Parenthesis(_)))
-                    {
-                        tokens.push(Self::Operator(Operator::Multiply));
-                    }
-                    if is_float {
-                        current_float.push(c);
-                    } else {
-                        current_number.push(c);
-                    }
-                }
-                '.' => {
-                    if is_parsing_parentesis {
-                        continue;
-                    }
-                    if is_float {
-                        return Err(ParsingError::indexed(
-                            "Invalid number".to_string(),
-                            input.to_string(),
-                            i,
-                        ));
-                    }
-                    // if current number is empty,
-                    // it means we are parsing a
-                    // float starting with a dot
-                    if current_number.is_empty() {
-                        current_number.push('0');
-                    }
-                    is_float = true;
-                }
-                '+' => {
-                    if is_parsing_parentesis {
-                        continue;
+        match c {
+            'x' | 'b' | 'o' if current_number == "0" && !is_float => {
+                let start = number_start;
+                current_number.clear();
+                radix = Some((
+                    match c {
+                        'x' => 16,
+                        'b' => 2,
+                        'o' => 8,
+                        _ => unreachable!(),
+                    },
+                    String::new(),
+                    start,
+                ));
+            }
+            'a'..='z' | 'A'..='Z' | '_' => {
+                if current_ident.is_empty() {
+                    // A letter right after a pending numeric literal means
+                    // implicit multiplication, e.g. `2x` is `2 * x`; flush
+                    // the number first so it doesn't get swallowed into the
+                    // identifier or reordered behind it.
+                    flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                    if needs_implicit_multiply(&tokens) {
+                        tokens.push((Lexeme::Operator(Operator::Multiply), i));
                     }
-                    compute_number(
-                        &mut current_number,
-                        &mut current_float,
-                        &mut tokens,
-                        &mut is_float,
-                        i,
-                    )?;
-                    tokens.push(Self::Operator(Operator::Add));
+                    ident_start = i;
                 }
-                '-' => {
-                    if is_parsing_parentesis {
-                        continue;
-                    }
-                    if current_number.is_empty() {
-                        // This is a negative number
-                        tokens.push(Self::Operator(Operator::Inverse));
-                    } else {
-                        compute_number(
-                            &mut current_number,
-                            &mut current_float,
-                            &mut tokens,
-                            &mut is_float,
-                            i,
-                        )?;
-                        tokens.push(Self::Operator(Operator::Subtract));
-                    }
+                current_ident.push(c);
+            }
+            '0'..='9' => {
+                if !current_ident.is_empty() {
+                    current_ident.push(c);
+                    continue;
                 }
-                '*' => {
-                    if is_parsing_parentesis {
-                        continue;
-                    }
-                    compute_number(
-                        &mut current_number,
-                        &mut current_float,
-                        &mut tokens,
-                        &mut is_float,
-                        i,
-                    )?;
-                    tokens.push(Self::Operator(Operator::Multiply));
+                if current_number.is_empty() && current_float.is_empty() && !is_float && needs_implicit_multiply(&tokens) {
+                    tokens.push((Lexeme::Operator(Operator::Multiply), i));
                 }
-                '/' => {
-                    if is_parsing_parentesis {
-                        continue;
-                    }
-                    compute_number(
-                        &mut current_number,
-                        &mut current_float,
-                        &mut tokens,
-                        &mut is_float,
-                        i,
-                    )?;
-                    tokens.push(Self::Operator(Operator::Divide));
+                if is_float {
+                    current_float.push(c);
+                } else {
+                    current_number.push(c);
                 }
-                '^' => {
-                    if is_parsing_parentesis {
-                        continue;
-                    }
-                    compute_number(
-                        &mut current_number,
-                        &mut current_float,
-                        &mut tokens,
-                        &mut is_float,
-                        i,
-                    )?;
-                    tokens.push(Self::Operator(Operator::Pow));
-                }
-                '(' => {
-                    // If previous token is a number,
-                    // we add a multiplication operator
-                    // and we parse the previous number
-                    if !current_number.is_empty() {
-                        compute_number(
-                            &mut current_number,
-                            &mut current_float,
-                            &mut tokens,
-                            &mut is_float,
-                            i,
-                        )?;
-                        tokens.push(Self::Operator(Operator::Multiply));
-                    }
-                    if parenthesis_depth == 0 {
-                        parenthesis_start = i;
-                        is_parsing_parentesis = true;
-                    }
-                    parenthesis_depth += 1;
+            }
+            '.' => {
+                if is_float {
+                    return Err(ParsingError::indexed("Invalid number".to_string(), input.to_string(), i));
                 }
-                ')' => {
-                    parenthesis_depth -= 1;
-                    if parenthesis_depth == 0 {
-                        // if parenthesis is empty, we return an error
-                        if parenthesis_start + 1 == i {
-                            return Err(ParsingError::indexed(
-                                "Empty parenthesis".to_string(),
-                                input.to_string(),
-                                i - 1,
-                            ));
-                        }
-                        is_parsing_parentesis = false;
-                        tokens.push(Self::Parenthesis(Self::tokenize(
-                            &input[parenthesis_start + 1..i],
-                        )?));
-                    }
+                // A float may start with a bare dot, e.g. `.5`.
+                if current_number.is_empty() {
+                    current_number.push('0');
                 }
-                ' ' => (), // Ignore spaces
-                _ => {
-                    return Err(ParsingError::indexed(
-                        "Invalid character".to_string(),
-                        input.to_string(),
-                        i,
-                    ))
+                is_float = true;
+            }
+            '=' if chars.get(i + 1) == Some(&'=') => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Eq), i));
+                skip_next = true;
+            }
+            '=' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Assign), i));
+            }
+            '!' if chars.get(i + 1) == Some(&'=') => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Neq), i));
+                skip_next = true;
+            }
+            '+' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Add), i));
+            }
+            '-' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Subtract), i));
+            }
+            '*' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Multiply), i));
+            }
+            '/' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Divide), i));
+            }
+            '^' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Pow), i));
+            }
+            '&' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::BitAnd), i));
+            }
+            '|' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::BitOr), i));
+            }
+            '<' if chars.get(i + 1) == Some(&'<') => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Shl), i));
+                skip_next = true;
+            }
+            '<' if chars.get(i + 1) == Some(&'=') => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Lte), i));
+                skip_next = true;
+            }
+            '<' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Lt), i));
+            }
+            '>' if chars.get(i + 1) == Some(&'>') => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Shr), i));
+                skip_next = true;
+            }
+            '>' if chars.get(i + 1) == Some(&'=') => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Gte), i));
+                skip_next = true;
+            }
+            '>' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Operator(Operator::Gt), i));
+            }
+            '(' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                if !current_ident.is_empty() {
+                    // A name directly before `(` is a function call; flush it
+                    // as the `Identifier` the parser will see right before `LParen`.
+                    tokens.push((Lexeme::Identifier(std::mem::take(&mut current_ident)), ident_start));
+                } else if needs_implicit_multiply(&tokens) {
+                    tokens.push((Lexeme::Operator(Operator::Multiply), i));
                 }
+                tokens.push((Lexeme::LParen, i));
             }
+            ')' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::RParen, i));
+            }
+            ',' => {
+                flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, i)?;
+                tokens.push((Lexeme::Comma, i));
+            }
+            ' ' | '\n' => (), // Ignore whitespace
+            _ => return Err(ParsingError::indexed("Invalid character".to_string(), input.to_string(), i)),
         }
+    }
 
-        if parenthesis_depth != 0 {
-            return Err(ParsingError::indexed(
-                "Parenthesis not closed".to_string(),
-                input.to_string(),
-                input.len() - 1,
-            ));
+    if let Some((base, digits, start)) = radix {
+        if digits.is_empty() {
+            return Err(ParsingError::indexed("Invalid integer literal".to_string(), input.to_string(), input.len()));
         }
+        let value = i64::from_str_radix(&digits, base)
+            .map_err(|_| ParsingError::indexed("Invalid integer literal".to_string(), input.to_string(), input.len()))?;
+        tokens.push((Lexeme::Number(value as f64), start));
+    }
 
-        if !current_number.is_empty() {
-            tokens.push(Self::Number(match Self::build_number(&current_number, &current_float) {
-                Ok(n) => n,
-                Err(_) => {
-                    return Err(ParsingError::indexed(
-                        "Invalid number".to_string(),
-                        input.to_string(),
-                        input.len(),
-                    ))
-                }
-            }));
+    if !current_ident.is_empty() {
+        tokens.push((Lexeme::Identifier(std::mem::take(&mut current_ident)), ident_start));
+    }
+
+    flush_number(&mut current_number, &mut current_float, &mut tokens, &mut is_float, number_start, input.len())?;
+
+    Ok(tokens)
+}
+
+// Echo the lexed expression back out before evaluating it.
+fn display(tokens: &[(Lexeme, usize)]) -> Result<i32, &str> {
+    println!(
+        "{}",
+        tokens.iter().map(|(t, _)| t.to_string()).collect::<Vec<String>>().join("")
+    );
+    Ok(0)
+}
+
+// A runtime value: either a number or the result of a comparison
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Number(_) => "number",
+            Self::Bool(_) => "bool",
         }
+    }
 
-        Ok(tokens)
+    fn as_number(self) -> Result<f64, Error> {
+        match self {
+            Self::Number(n) => Ok(n),
+            Self::Bool(_) => Err(Error::WrongTypeCombination {
+                expected: "number".to_string(),
+                found: self.type_name().to_string(),
+            }),
+        }
     }
 }
 
-impl std::fmt::Display for ParsingToken {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Number(n) => write!(f, "{}", n),
-            Self::Operator(o) => write!(f, " {} ", o),
-            Self::Parenthesis(p) => write!(
-                f,
-                "({})",
-                p.iter()
-                    .map(|t| t.to_string())
-                    .collect::<Vec<String>>()
-                    .join("")
-            ),
+            Self::Bool(b) => write!(f, "{}", b),
         }
     }
 }
 
-fn display(tokens: &Vec<ParsingToken>) -> Result<i32, &str> {
-    println!(
-        "{}",
-        tokens
-            .iter()
-            .map(|t| t.to_string())
-            .collect::<Vec<String>>()
-            .join("")
-    );
-    Ok(0)
+// Evaluation-time error, as opposed to `ParsingError` which covers tokenizing and parsing
+#[derive(Debug)]
+enum Error {
+    WrongTypeCombination { expected: String, found: String },
+    Message(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongTypeCombination { expected, found } => {
+                write!(f, "Wrong type combination: expected {}, found {}", expected, found)
+            }
+            Self::Message(m) => write!(f, "{}", m),
+        }
+    }
 }
 
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Self::Message(message)
+    }
+}
+
+#[derive(Clone)]
 struct Function {
     signature: String,
     arguments_count: i32,
-    function: fn(Vec<f64>) -> f64,
+    // When true, `arguments_count` is a minimum rather than an exact count
+    variadic: bool,
+    function: fn(Vec<Value>) -> Result<Value, Error>,
 }
 
 impl Function {
 
     fn from_operator(operator: &Operator) -> Self {
         match operator {
+            // Assignment is resolved directly in `Token::new`, it never reaches a `Function`
+            Operator::Assign => unreachable!("assignment has no Function representation"),
+            Operator::BitOr => Self::bor(),
+            Operator::BitAnd => Self::band(),
+            Operator::Eq => Self::eq(),
+            Operator::Neq => Self::neq(),
+            Operator::Lt => Self::lt(),
+            Operator::Lte => Self::lte(),
+            Operator::Gt => Self::gt(),
+            Operator::Gte => Self::gte(),
+            Operator::Shl => Self::shl(),
+            Operator::Shr => Self::shr(),
             Operator::Add => Self::add(),
             Operator::Subtract => Self::subtract(),
             Operator::Multiply => Self::multiply(),
@@ -397,31 +593,86 @@ impl Function {
         }
     }
 
-    fn new(signature: String, arguments_count: i32, function: fn(Vec<f64>) -> f64) -> Self {
+    // Every named function callable from an expression, keyed by signature.
+    fn builtins() -> HashMap<String, Self> {
+        let mut builtins = HashMap::new();
+        for f in [
+            Self::sin(),
+            Self::cos(),
+            Self::tan(),
+            Self::sqrt(),
+            Self::ln(),
+            Self::log(),
+            Self::abs(),
+            Self::min(),
+            Self::max(),
+            Self::floor(),
+            Self::ceil(),
+        ] {
+            builtins.insert(f.signature.clone(), f);
+        }
+        builtins
+    }
+
+    fn new(signature: String, arguments_count: i32, function: fn(Vec<Value>) -> Result<Value, Error>) -> Self {
         Self {
             signature,
             arguments_count,
+            variadic: false,
+            function,
+        }
+    }
+
+    fn new_variadic(
+        signature: String,
+        min_arguments_count: i32,
+        function: fn(Vec<Value>) -> Result<Value, Error>,
+    ) -> Self {
+        Self {
+            signature,
+            arguments_count: min_arguments_count,
+            variadic: true,
             function,
         }
     }
 
-    fn call(&self, arguments: Vec<f64>) -> Result<f64, String> {
-        if arguments.len() != self.arguments_count as usize {
-            return Err(format!(
-                "Invalid number of arguments for function {}, expected {}, found {}",
+    fn call(&self, arguments: Vec<Value>) -> Result<Value, Error> {
+        let invalid_count = if self.variadic {
+            arguments.len() < self.arguments_count as usize
+        } else {
+            arguments.len() != self.arguments_count as usize
+        };
+        if invalid_count {
+            return Err(Error::Message(format!(
+                "Invalid number of arguments for function {}, expected {}{}, found {}",
                 self.signature,
+                if self.variadic { "at least " } else { "" },
                 self.arguments_count,
                 arguments.len()
-            ));
+            )));
+        }
+        (self.function)(arguments)
+    }
+
+    // Bitwise operators work on integers; reject operands with a fractional part.
+    fn require_integer(n: f64) -> Result<i64, Error> {
+        if n.fract() != 0.0 {
+            return Err(Error::Message(format!("Expected an integer, found {}", n)));
         }
-        Ok((self.function)(arguments))
+        Ok(n as i64)
+    }
+
+    // Shl/shr panic on a negative or out-of-range shift amount; reject those
+    // with a clean `Error` instead of letting the overflow check abort.
+    fn require_shift_amount(n: i64) -> Result<u32, Error> {
+        u32::try_from(n).map_err(|_| Error::Message(format!("Invalid shift amount: {}", n)))
     }
 
     fn add() -> Self {
         Self::new(
             "add".to_string(),
             2,
-            |arguments: Vec<f64>| arguments[0] + arguments[1],
+            |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()? + arguments[1].as_number()?)),
         )
     }
 
@@ -429,7 +680,7 @@ impl Function {
         Self::new(
             "sub".to_string(),
             2,
-            |arguments: Vec<f64>| arguments[0] - arguments[1],
+            |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()? - arguments[1].as_number()?)),
         )
     }
 
@@ -437,7 +688,7 @@ impl Function {
         Self::new(
             "mul".to_string(),
             2,
-            |arguments: Vec<f64>| arguments[0] * arguments[1],
+            |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()? * arguments[1].as_number()?)),
         )
     }
 
@@ -445,7 +696,7 @@ impl Function {
         Self::new(
             "div".to_string(),
             2,
-            |arguments: Vec<f64>| arguments[0] / arguments[1],
+            |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()? / arguments[1].as_number()?)),
         )
     }
 
@@ -453,7 +704,7 @@ impl Function {
         Self::new(
             "inv".to_string(),
             1,
-            |arguments: Vec<f64>| -arguments[0]
+            |arguments: Vec<Value>| Ok(Value::Number(-arguments[0].as_number()?))
         )
     }
 
@@ -461,97 +712,319 @@ impl Function {
         Self::new(
             "pow".to_string(),
             2,
-            |arguments: Vec<f64>| arguments[0].powf(arguments[1]),
+            |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.powf(arguments[1].as_number()?))),
         )
     }
+
+    fn band() -> Self {
+        Self::new("band".to_string(), 2, |arguments: Vec<Value>| {
+            let a = Self::require_integer(arguments[0].as_number()?)?;
+            let b = Self::require_integer(arguments[1].as_number()?)?;
+            Ok(Value::Number((a & b) as f64))
+        })
+    }
+
+    fn bor() -> Self {
+        Self::new("bor".to_string(), 2, |arguments: Vec<Value>| {
+            let a = Self::require_integer(arguments[0].as_number()?)?;
+            let b = Self::require_integer(arguments[1].as_number()?)?;
+            Ok(Value::Number((a | b) as f64))
+        })
+    }
+
+    fn shl() -> Self {
+        Self::new("shl".to_string(), 2, |arguments: Vec<Value>| {
+            let a = Self::require_integer(arguments[0].as_number()?)?;
+            let b = Self::require_shift_amount(Self::require_integer(arguments[1].as_number()?)?)?;
+            let result = a.checked_shl(b).ok_or_else(|| Error::Message(format!("Invalid shift amount: {}", b)))?;
+            Ok(Value::Number(result as f64))
+        })
+    }
+
+    fn shr() -> Self {
+        Self::new("shr".to_string(), 2, |arguments: Vec<Value>| {
+            let a = Self::require_integer(arguments[0].as_number()?)?;
+            let b = Self::require_shift_amount(Self::require_integer(arguments[1].as_number()?)?)?;
+            let result = a.checked_shr(b).ok_or_else(|| Error::Message(format!("Invalid shift amount: {}", b)))?;
+            Ok(Value::Number(result as f64))
+        })
+    }
+
+    // Equality compares `Value`s directly, so e.g. `true == 1` is `false` rather than an error.
+    fn eq() -> Self {
+        Self::new("eq".to_string(), 2, |arguments: Vec<Value>| {
+            Ok(Value::Bool(arguments[0] == arguments[1]))
+        })
+    }
+
+    fn neq() -> Self {
+        Self::new("neq".to_string(), 2, |arguments: Vec<Value>| {
+            Ok(Value::Bool(arguments[0] != arguments[1]))
+        })
+    }
+
+    // Ordering only makes sense between numbers, so these go through `as_number`
+    // and error on e.g. `true < false`.
+    fn lt() -> Self {
+        Self::new("lt".to_string(), 2, |arguments: Vec<Value>| {
+            Ok(Value::Bool(arguments[0].as_number()? < arguments[1].as_number()?))
+        })
+    }
+
+    fn lte() -> Self {
+        Self::new("lte".to_string(), 2, |arguments: Vec<Value>| {
+            Ok(Value::Bool(arguments[0].as_number()? <= arguments[1].as_number()?))
+        })
+    }
+
+    fn gt() -> Self {
+        Self::new("gt".to_string(), 2, |arguments: Vec<Value>| {
+            Ok(Value::Bool(arguments[0].as_number()? > arguments[1].as_number()?))
+        })
+    }
+
+    fn gte() -> Self {
+        Self::new("gte".to_string(), 2, |arguments: Vec<Value>| {
+            Ok(Value::Bool(arguments[0].as_number()? >= arguments[1].as_number()?))
+        })
+    }
+
+    fn sin() -> Self {
+        Self::new("sin".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.sin())))
+    }
+
+    fn cos() -> Self {
+        Self::new("cos".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.cos())))
+    }
+
+    fn tan() -> Self {
+        Self::new("tan".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.tan())))
+    }
+
+    fn sqrt() -> Self {
+        Self::new("sqrt".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.sqrt())))
+    }
+
+    fn ln() -> Self {
+        Self::new("ln".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.ln())))
+    }
+
+    fn log() -> Self {
+        Self::new("log".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.log10())))
+    }
+
+    fn abs() -> Self {
+        Self::new("abs".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.abs())))
+    }
+
+    fn floor() -> Self {
+        Self::new("floor".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.floor())))
+    }
+
+    fn ceil() -> Self {
+        Self::new("ceil".to_string(), 1, |arguments: Vec<Value>| Ok(Value::Number(arguments[0].as_number()?.ceil())))
+    }
+
+    fn min() -> Self {
+        Self::new_variadic("min".to_string(), 2, |arguments: Vec<Value>| {
+            let numbers = arguments.into_iter().map(Value::as_number).collect::<Result<Vec<f64>, Error>>()?;
+            Ok(Value::Number(numbers.into_iter().fold(f64::INFINITY, f64::min)))
+        })
+    }
+
+    fn max() -> Self {
+        Self::new_variadic("max".to_string(), 2, |arguments: Vec<Value>| {
+            let numbers = arguments.into_iter().map(Value::as_number).collect::<Result<Vec<f64>, Error>>()?;
+            Ok(Value::Number(numbers.into_iter().fold(f64::NEG_INFINITY, f64::max)))
+        })
+    }
+}
+
+// Maps variable names to their current value across a sequence of evaluations
+type Environment = HashMap<String, Value>;
+
+fn default_environment() -> Environment {
+    let mut env = Environment::new();
+    env.insert("pi".to_string(), Value::Number(std::f64::consts::PI));
+    env.insert("e".to_string(), Value::Number(std::f64::consts::E));
+    env
 }
 
 enum Token {
     Operator(Function, Vec<Token>),
-    Number(f64)
+    Number(f64),
+    Variable(String),
+    Assign(String, Box<Token>),
 }
 
 impl Token {
 
-    fn new(input: &[ParsingToken]) -> Result<Self, ParsingError> {
-        // If len == 1, we have a number
-        if input.len() == 1 {
-            return match input[0] {
-                ParsingToken::Number(n) => Ok(Self::Number(n)),
-                ParsingToken::Parenthesis(ref p) => Self::new(p),
-                _ => Err(ParsingError::not_indexed(
-                    format!("Invalid token: {}, expected number", input[0])
-                )),
-            }
-        }
-
-        let mut lowest_priority: Option<i32> = None;
-        let mut lowest_priority_index: Option<usize> = None;
-        for (i, t) in input.iter().enumerate() {
-            match t {
-                ParsingToken::Operator(o) => {
-                    if lowest_priority.is_none() {
-                        lowest_priority = Some(o.priority());
-                        lowest_priority_index = Some(i);
-                    } else if o.priority() < lowest_priority.unwrap() {
-                        // Not >= because we want to keep the leftmost operator
-                        lowest_priority = Some(o.priority());
-                        lowest_priority_index = Some(i);
+    // Parse a full expression out of `input` (as lexed from `source`) and
+    // make sure every lexeme was consumed.
+    fn new(source: &str, input: &[(Lexeme, usize)]) -> Result<Self, ParsingError> {
+        let mut pos = 0;
+        let token = Self::parse_expr(source, input, &mut pos, 0)?;
+        if let Some((lexeme, offset)) = input.get(pos) {
+            return Err(ParsingError::indexed(
+                format!("Unexpected token: {}", lexeme),
+                source.to_string(),
+                *offset,
+            ));
+        }
+        Ok(token)
+    }
+
+    // Pratt parser. Parses one prefix, then repeatedly folds in infix
+    // operators whose left binding power is at least `min_bp`, recursing
+    // with the operator's right binding power to parse its operand.
+    fn parse_expr(
+        source: &str,
+        input: &[(Lexeme, usize)],
+        pos: &mut usize,
+        min_bp: u8,
+    ) -> Result<Self, ParsingError> {
+        let mut lhs = Self::parse_prefix(source, input, pos)?;
+
+        loop {
+            let operator = match input.get(*pos) {
+                Some((Lexeme::Operator(o), _)) => *o,
+                _ => break,
+            };
+            let (lbp, rbp) = operator.binding_power();
+            if lbp < min_bp {
+                break;
+            }
+            *pos += 1;
+
+            if operator == Operator::Assign {
+                let name = match lhs {
+                    Self::Variable(name) => name,
+                    _ => {
+                        return Err(ParsingError::not_indexed(
+                            "Left-hand side of '=' must be a single variable name".to_string(),
+                        ))
                     }
-                }
-                _ => (),
+                };
+                let value = Self::parse_expr(source, input, pos, rbp)?;
+                lhs = Self::Assign(name, Box::new(value));
+                continue;
             }
-        }
 
-        // If still None, we have many numbers
-        // It can't be a valid input
-        if lowest_priority.is_none() {
-            return Err(ParsingError::not_indexed(
-                format!("There are many numbers in the input, expected operator")
-            ));
+            let rhs = Self::parse_expr(source, input, pos, rbp)?;
+            lhs = Self::Operator(Function::from_operator(&operator), vec![lhs, rhs]);
         }
 
-        match &input[lowest_priority_index.unwrap()] {
-            ParsingToken::Operator(o) => {
-                match o.count() {
-                    1 => {
-                        // Unary operator
-                        let right = Self::new(&input[lowest_priority_index.unwrap() + 1..])?;
-                        Ok(
-                            Self::Operator(
-                                Function::from_operator(o),
-                                vec![right]
-                            )
-                        )
-                    }
-                    2 => {
-                        // Binary operator
-                        let left = Self::new(&input[..lowest_priority_index.unwrap()])?;
-                        let right = Self::new(&input[lowest_priority_index.unwrap() + 1..])?;
-                        Ok(
-                            Self::Operator(
-                                Function::from_operator(o),
-                                vec![left, right]
-                            )
-                        )
+        Ok(lhs)
+    }
+
+    // Parses a number, a variable or function call, a parenthesized
+    // sub-expression, or a unary `-`/`+`.
+    fn parse_prefix(
+        source: &str,
+        input: &[(Lexeme, usize)],
+        pos: &mut usize,
+    ) -> Result<Self, ParsingError> {
+        let (lexeme, offset) = input.get(*pos).ok_or_else(|| {
+            ParsingError::indexed(
+                "Unexpected end of input".to_string(),
+                source.to_string(),
+                source.len(),
+            )
+        })?;
+        let offset = *offset;
+
+        match lexeme {
+            Lexeme::Number(n) => {
+                *pos += 1;
+                Ok(Self::Number(*n))
+            }
+            Lexeme::Operator(Operator::Subtract) => {
+                *pos += 1;
+                let operand = Self::parse_expr(source, input, pos, UNARY_BINDING_POWER)?;
+                Ok(Self::Operator(Function::from_operator(&Operator::Inverse), vec![operand]))
+            }
+            Lexeme::Operator(Operator::Add) => {
+                // Unary plus is a no-op.
+                *pos += 1;
+                Self::parse_expr(source, input, pos, UNARY_BINDING_POWER)
+            }
+            Lexeme::LParen => {
+                *pos += 1;
+                let inner = Self::parse_expr(source, input, pos, 0)?;
+                match input.get(*pos) {
+                    Some((Lexeme::RParen, _)) => {
+                        *pos += 1;
+                        Ok(inner)
                     }
-                    _ => Err(ParsingError::not_indexed(
-                        format!("Operator with more than 2 arguments are not supported, found {}", o.count())
+                    _ => Err(ParsingError::spanned(
+                        "Parenthesis not closed".to_string(),
+                        source.to_string(),
+                        input.get(*pos).map_or(source.len(), |(_, o)| *o),
+                        offset,
                     )),
                 }
             }
-            _ => Err(ParsingError::not_indexed(
-                format!("Invalid token: {}, expected operator", input[lowest_priority_index.unwrap()])
+            Lexeme::Identifier(name) => {
+                let name = name.clone();
+                *pos += 1;
+                if !matches!(input.get(*pos), Some((Lexeme::LParen, _))) {
+                    return Ok(Self::Variable(name));
+                }
+                *pos += 1;
+
+                let function = Function::builtins().remove(&name).ok_or_else(|| {
+                    ParsingError::indexed(format!("Unknown function: {}", name), source.to_string(), offset)
+                })?;
+
+                let mut arguments = Vec::new();
+                if !matches!(input.get(*pos), Some((Lexeme::RParen, _))) {
+                    loop {
+                        arguments.push(Self::parse_expr(source, input, pos, 0)?);
+                        match input.get(*pos) {
+                            Some((Lexeme::Comma, _)) => *pos += 1,
+                            _ => break,
+                        }
+                    }
+                }
+                match input.get(*pos) {
+                    Some((Lexeme::RParen, _)) => *pos += 1,
+                    _ => {
+                        return Err(ParsingError::spanned(
+                            "Parenthesis not closed".to_string(),
+                            source.to_string(),
+                            input.get(*pos).map_or(source.len(), |(_, o)| *o),
+                            offset,
+                        ))
+                    }
+                }
+                Ok(Self::Operator(function, arguments))
+            }
+            _ => Err(ParsingError::indexed(
+                format!("Invalid token: {}, expected number", lexeme),
+                source.to_string(),
+                offset,
             )),
         }
     }
 
-    fn compute(&self) -> Result<f64, String> {
+    fn compute(&self, env: &mut Environment) -> Result<Value, Error> {
         match self {
-            Self::Number(n) => Ok(*n),
-            Self::Operator(f, arguments) => match f.call(arguments.iter().map(|t| t.compute()?).collect()) {
-                Ok(n) => Ok(n),
-                Err(e) => Err(e),
+            Self::Number(n) => Ok(Value::Number(*n)),
+            Self::Variable(name) => env
+                .get(name)
+                .copied()
+                .ok_or_else(|| Error::Message(format!("Unknown variable: {}", name))),
+            Self::Assign(name, value) => {
+                let value = value.compute(env)?;
+                env.insert(name.clone(), value);
+                Ok(value)
+            }
+            Self::Operator(f, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|t| t.compute(env))
+                    .collect::<Result<Vec<Value>, Error>>()?;
+                f.call(arguments)
             }
         }
     }
@@ -561,6 +1034,8 @@ impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::Number(n) => write!(f, "{}", n),
+            Self::Variable(name) => write!(f, "{}", name),
+            Self::Assign(name, value) => write!(f, "{} = {}", name, value),
             Self::Operator(o, arguments) => {
                 write!(f, "{}", o.signature)?;
                 write!(f, "(")?;
@@ -578,25 +1053,16 @@ impl std::fmt::Display for Token {
 
 
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    match ParsingToken::tokenize(
-        if args.len() > 1 {
-            // Merge all input from 1 to ..
-            args[1..].join(" ")
-        } else {
-            // Token test
-            "1 + 2 * 3 / 2 - 4".to_string()
-        }.as_str()
-    ) {
+// Tokenize, parse and evaluate a single expression against `env`, printing
+// its pretty form and result (or any error) the way the one-shot CLI does.
+fn evaluate(input: &str, env: &mut Environment) {
+    match lex(input) {
         Ok(tokens) => {
             display(&tokens).unwrap();
-            match Token::new(&tokens) {
-                Ok(t) => {
-                    match t.compute() {
-                        Ok(n) => println!("{} = {}", t, n),
-                        Err(e) => println!("{}", e),
-                    }
+            match Token::new(input, &tokens) {
+                Ok(t) => match t.compute(env) {
+                    Ok(n) => println!("{} = {}", t, n),
+                    Err(e) => println!("{}", e),
                 },
                 Err(e) => {
                     println!("{}", e);
@@ -608,3 +1074,52 @@ fn main() {
         }
     }
 }
+
+// Interactive desk-calculator loop: each line is evaluated against the same
+// environment, so a variable assigned on one line is visible on the next.
+// An empty line or `quit` ends the session; a parsing error only aborts
+// that line.
+fn repl() {
+    use std::io::Write;
+
+    let mut env = default_environment();
+    let mut line = String::new();
+
+    loop {
+        print!("> ");
+        std::io::stdout().flush().unwrap();
+
+        line.clear();
+        if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+
+        let line = line.trim();
+        if line.is_empty() || line == "quit" {
+            break;
+        }
+
+        evaluate(line, &mut env);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.get(1).map_or(false, |a| a == "--repl") {
+        repl();
+        return;
+    }
+
+    let mut env = default_environment();
+    evaluate(
+        if args.len() > 1 {
+            // Merge all input from 1 to ..
+            args[1..].join(" ")
+        } else {
+            // Token test
+            "1 + 2 * 3 / 2 - 4".to_string()
+        }.as_str(),
+        &mut env,
+    );
+}